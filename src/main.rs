@@ -1,27 +1,163 @@
+use std::{
+	borrow::Cow,
+	collections::HashMap,
+	convert::Infallible,
+	fmt,
+	fs::{self, OpenOptions},
+	hash::Hash,
+	io::Write,
+	marker::PhantomData,
+	num::{NonZeroU8, NonZeroUsize, TryFromIntError},
+	panic::{self, AssertUnwindSafe},
+	path::Path,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		mpsc, Arc, Mutex, MutexGuard, PoisonError,
+	},
+	thread,
+	time::{Duration, Instant},
+};
+
 mod private {
 	pub struct WithParts;
 	pub struct WithRequest;
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
 struct RequestParts {
 	count: u8,
 }
 
-#[derive(Clone)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 struct Request {
 	parts: RequestParts,
 	expensive: Vec<u8>,
 }
 
 struct Response {
-	content: String,
+	body: ResponseBody,
+}
+
+enum ResponseBody {
+	Empty,
+	Full(Cow<'static, [u8]>),
+	Stream(Box<dyn Iterator<Item = Vec<u8>>>),
+}
+
+impl ResponseBody {
+	fn into_bytes(self) -> Vec<u8> {
+		match self {
+			Self::Empty => Vec::new(),
+			Self::Full(bytes) => bytes.into_owned(),
+			Self::Stream(chunks) => chunks.flatten().collect(),
+		}
+	}
+}
+
+#[derive(serde::Serialize)]
+struct ResponseSnapshot {
+	body: SnapshotBody,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum SnapshotBody {
+	Empty,
+	Text(String),
+	Binary(Vec<u8>),
 }
 
+impl Response {
+	fn static_str(text: &'static str) -> Self {
+		Self { body: text.into() }
+	}
+
+	fn snapshot(self) -> ResponseSnapshot {
+		let bytes = self.body.into_bytes();
+		let body = if bytes.is_empty() {
+			SnapshotBody::Empty
+		} else {
+			match String::from_utf8(bytes) {
+				Ok(text) => SnapshotBody::Text(text),
+				Err(err) => SnapshotBody::Binary(err.into_bytes()),
+			}
+		};
+
+		ResponseSnapshot { body }
+	}
+}
+
+impl From<()> for ResponseBody {
+	fn from(_: ()) -> Self {
+		Self::Empty
+	}
+}
+
+impl From<()> for Response {
+	fn from(_: ()) -> Self {
+		Self { body: ().into() }
+	}
+}
+
+impl From<Infallible> for Response {
+	fn from(never: Infallible) -> Self {
+		match never {}
+	}
+}
+
+impl From<Vec<u8>> for ResponseBody {
+	fn from(bytes: Vec<u8>) -> Self {
+		Self::Full(Cow::Owned(bytes))
+	}
+}
+
+impl From<String> for ResponseBody {
+	fn from(text: String) -> Self {
+		Self::Full(Cow::Owned(text.into_bytes()))
+	}
+}
+
+impl From<&'static str> for ResponseBody {
+	fn from(text: &'static str) -> Self {
+		Self::Full(Cow::Borrowed(text.as_bytes()))
+	}
+}
+
+/// Extractors may write back into the parts they receive. Handlers run their
+/// extractors left to right, so later arguments observe earlier changes.
 trait FromRequestParts<S> {
 	fn from_request_parts(parts: &mut RequestParts, state: S) -> Self;
 }
 
+/// A body that can be reshaped but not moved out of, so whatever an extractor
+/// leaves behind is handed on to the body extractor after it.
+struct BodyMut<'a>(&'a mut Vec<u8>);
+
+impl BodyMut<'_> {
+	fn as_mut_slice(&mut self) -> &mut [u8] {
+		self.0
+	}
+
+	fn truncate(&mut self, len: usize) {
+		self.0.truncate(len);
+	}
+
+	fn drain_front(&mut self, n: usize) -> Vec<u8> {
+		let n = n.min(self.0.len());
+		self.0.drain(..n).collect()
+	}
+
+	fn replace(&mut self, body: Vec<u8>) {
+		*self.0 = body;
+	}
+}
+
+/// Extractors may consume a prefix of the body or rewrite it, and the rest is
+/// passed on to the body extractor after them.
+trait FromRequestMut<S, X = private::WithRequest> {
+	fn from_request_mut(parts: &mut RequestParts, body: BodyMut<'_>, state: S) -> Self;
+}
+
 trait FromRequest<S, X = private::WithRequest> {
 	fn from_request(req: Request, state: S) -> Self;
 }
@@ -39,42 +175,73 @@ where
 	}
 }
 
+impl<T, S> FromRequestMut<S, private::WithParts> for T
+where
+	T: FromRequestParts<S>,
+{
+	fn from_request_mut(parts: &mut RequestParts, _: BodyMut<'_>, state: S) -> Self {
+		T::from_request_parts(parts, state)
+	}
+}
+
+/// Any `TryFrom<&RequestParts>` type is an extractor. There is no rejection
+/// type, so a failed conversion panics with the type's name, just as `Json`
+/// panics on invalid input. The error itself is left out, since it may hold
+/// the raw value of a `Sensitive` extractor.
+impl<S, T> FromRequestParts<S> for T
+where
+	T: for<'a> TryFrom<&'a RequestParts>,
+{
+	fn from_request_parts(parts: &mut RequestParts, _: S) -> Self {
+		T::try_from(parts).unwrap_or_else(|_| {
+			panic!(
+				"expected valid request parts for {}",
+				std::any::type_name::<T>()
+			)
+		})
+	}
+}
+
 impl<S> FromRequestParts<S> for () {
 	fn from_request_parts(_: &mut RequestParts, _: S) -> Self {}
 }
 
-impl<S, F> Handler<(), S> for F
+impl<S, F, R> Handler<(), S> for F
 where
-	F: Fn() -> Response,
+	F: Fn() -> R,
+	R: Into<Response>,
 {
 	fn call(self, _: Request, _: S) -> Response {
-		self()
+		self().into()
 	}
 }
 
-impl<S, F, M, T1> Handler<(M, T1), S> for F
+impl<S, F, R, M, T1> Handler<(M, T1), S> for F
 where
-	F: FnOnce(T1) -> Response,
+	F: FnOnce(T1) -> R,
+	R: Into<Response>,
 	T1: FromRequest<S, M>,
 {
 	fn call(self, req: Request, state: S) -> Response {
 		let t1 = T1::from_request(req, state);
-		self(t1)
+		self(t1).into()
 	}
 }
 
-impl<S, F, M, T1, T2> Handler<(M, T1, T2), S> for F
+impl<S, F, R, M1, M2, T1, T2> Handler<(M1, M2, T1, T2), S> for F
 where
-	F: FnOnce(T1, T2) -> Response,
+	F: FnOnce(T1, T2) -> R,
+	R: Into<Response>,
 	S: Clone,
-	T1: FromRequestParts<S>,
-	T2: FromRequest<S, M>,
+	T1: FromRequestMut<S, M1>,
+	T2: FromRequest<S, M2>,
 {
 	fn call(self, mut req: Request, state: S) -> Response {
-		let t1 = T1::from_request_parts(&mut req.parts, state.clone());
+		let body = BodyMut(&mut req.expensive);
+		let t1 = T1::from_request_mut(&mut req.parts, body, state.clone());
 		let t2 = T2::from_request(req, state);
 
-		self(t1, t2)
+		self(t1, t2).into()
 	}
 }
 
@@ -94,6 +261,139 @@ impl<S> FromRequestParts<S> for Count {
 	}
 }
 
+trait FeatureName {
+	const NAME: &'static str;
+}
+
+trait FeatureFlags {
+	fn is_enabled(&self, name: &str, parts: &RequestParts) -> bool;
+}
+
+struct Feature<F>(bool, PhantomData<F>);
+
+impl<S, F> FromRequestParts<S> for Feature<F>
+where
+	S: FeatureFlags,
+	F: FeatureName,
+{
+	fn from_request_parts(parts: &mut RequestParts, flags: S) -> Self {
+		Self(flags.is_enabled(F::NAME, parts), PhantomData)
+	}
+}
+
+struct NonZeroCount(NonZeroU8);
+
+impl TryFrom<&RequestParts> for NonZeroCount {
+	type Error = TryFromIntError;
+
+	fn try_from(parts: &RequestParts) -> Result<Self, Self::Error> {
+		NonZeroU8::try_from(parts.count).map(Self)
+	}
+}
+
+struct Token(u8);
+
+impl TryFrom<&RequestParts> for Token {
+	type Error = String;
+
+	fn try_from(parts: &RequestParts) -> Result<Self, Self::Error> {
+		if parts.count.is_multiple_of(2) {
+			Ok(Self(parts.count))
+		} else {
+			Err(format!("bad token secret-{}", parts.count))
+		}
+	}
+}
+
+struct Visit;
+
+impl<S> FromRequestParts<S> for Visit {
+	fn from_request_parts(parts: &mut RequestParts, _: S) -> Self {
+		parts.count = parts.count.saturating_add(1);
+		Self
+	}
+}
+
+type MemoKeyFn<K> = Box<dyn Fn(&RequestParts) -> K + Send + Sync>;
+type MemoComputeFn<K, V> = Box<dyn Fn(&K) -> V + Send + Sync>;
+
+struct MemoEntries<K, V> {
+	values: HashMap<K, (Instant, V)>,
+	last_sweep: Instant,
+}
+
+/// Expired entries are swept at most once per `ttl`, so the cache has no
+/// capacity limit and holds every key seen within that window.
+struct MemoCache<K, V> {
+	ttl: Duration,
+	key: MemoKeyFn<K>,
+	compute: MemoComputeFn<K, V>,
+	entries: Mutex<MemoEntries<K, V>>,
+}
+
+impl<K, V> MemoCache<K, V>
+where
+	K: Eq + Hash,
+	V: Clone,
+{
+	fn new(
+		ttl: Duration,
+		key: impl Fn(&RequestParts) -> K + Send + Sync + 'static,
+		compute: impl Fn(&K) -> V + Send + Sync + 'static,
+	) -> Self {
+		Self {
+			ttl,
+			key: Box::new(key),
+			compute: Box::new(compute),
+			entries: Mutex::new(MemoEntries {
+				values: HashMap::new(),
+				last_sweep: Instant::now(),
+			}),
+		}
+	}
+
+	fn entries(&self) -> MutexGuard<'_, MemoEntries<K, V>> {
+		self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	fn get(&self, parts: &RequestParts) -> V {
+		let key = (self.key)(parts);
+
+		if let Some((computed_at, value)) = self.entries().values.get(&key) {
+			if computed_at.elapsed() < self.ttl {
+				return value.clone();
+			}
+		}
+
+		let value = (self.compute)(&key);
+		let mut entries = self.entries();
+
+		if entries.last_sweep.elapsed() >= self.ttl {
+			entries
+				.values
+				.retain(|_, (computed_at, _)| computed_at.elapsed() < self.ttl);
+			entries.last_sweep = Instant::now();
+		}
+
+		entries.values.insert(key, (Instant::now(), value.clone()));
+
+		value
+	}
+}
+
+struct Memo<K, V>(V, PhantomData<K>);
+
+impl<S, K, V> FromRequestParts<S> for Memo<K, V>
+where
+	S: AsRef<MemoCache<K, V>>,
+	K: Eq + Hash,
+	V: Clone,
+{
+	fn from_request_parts(parts: &mut RequestParts, state: S) -> Self {
+		Self(state.as_ref().get(parts), PhantomData)
+	}
+}
+
 struct Expensive(Vec<u8>);
 
 impl<S> FromRequest<S> for Expensive {
@@ -102,6 +402,68 @@ impl<S> FromRequest<S> for Expensive {
 	}
 }
 
+struct Decrypted;
+
+impl FromRequestMut<u8> for Decrypted {
+	fn from_request_mut(_: &mut RequestParts, mut body: BodyMut<'_>, key: u8) -> Self {
+		let nonce = body.drain_front(1);
+		let key = nonce.first().map_or(key, |nonce| key ^ nonce);
+		let tagged = body.as_mut_slice().len();
+
+		body.truncate(tagged.saturating_sub(1));
+
+		for byte in body.as_mut_slice() {
+			*byte ^= key;
+		}
+
+		Self
+	}
+}
+
+struct Unhex;
+
+impl<S> FromRequestMut<S> for Unhex {
+	fn from_request_mut(_: &mut RequestParts, mut body: BodyMut<'_>, _: S) -> Self {
+		let decoded = body
+			.as_mut_slice()
+			.chunks(2)
+			.map(|pair| {
+				let pair = std::str::from_utf8(pair).expect("expected valid hex");
+				u8::from_str_radix(pair, 16).expect("expected valid hex")
+			})
+			.collect();
+
+		body.replace(decoded);
+		Self
+	}
+}
+
+struct Sensitive<T>(T);
+
+impl<T> fmt::Debug for Sensitive<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("[redacted]")
+	}
+}
+
+impl<S, T> FromRequestParts<S> for Sensitive<T>
+where
+	T: FromRequestParts<S>,
+{
+	fn from_request_parts(parts: &mut RequestParts, state: S) -> Self {
+		Self(T::from_request_parts(parts, state))
+	}
+}
+
+impl<S, T> FromRequest<S> for Sensitive<T>
+where
+	T: FromRequest<S>,
+{
+	fn from_request(req: Request, state: S) -> Self {
+		Self(T::from_request(req, state))
+	}
+}
+
 struct Json<T>(T);
 
 impl<S, T> FromRequest<S> for Json<T>
@@ -114,20 +476,30 @@ where
 }
 
 fn simple() -> Response {
+	Response::static_str("Hello, world!")
+}
+
+fn with_count_and_state(State(state): State<u8>, Count(count): Count) -> Response {
 	Response {
-		content: "Hello, world!".to_string(),
+		body: format!("state: {state}, count: {count}").into(),
 	}
 }
 
-fn with_count_and_state(State(state): State<u8>, Count(count): Count) -> Response {
+fn with_non_zero_count(NonZeroCount(count): NonZeroCount) -> Response {
 	Response {
-		content: format!("state: {state}, count: {count}"),
+		body: format!("count: {count}").into(),
+	}
+}
+
+fn with_visit_and_count(_: Visit, Count(count): Count) -> Response {
+	Response {
+		body: format!("count: {count}").into(),
 	}
 }
 
 fn with_state_and_expensive(State(state): State<u8>, Expensive(expensive): Expensive) -> Response {
 	Response {
-		content: format!("state: {state}, expensive: {}", expensive.len()),
+		body: format!("state: {state}, expensive: {}", expensive.len()).into(),
 	}
 }
 
@@ -139,7 +511,120 @@ struct Body {
 
 fn with_json(Json(body): Json<Body>) -> Response {
 	Response {
-		content: body.text.repeat(body.repeat),
+		body: body.text.repeat(body.repeat).into(),
+	}
+}
+
+fn with_decrypted_json(_: Decrypted, Json(body): Json<Body>) -> Response {
+	Response {
+		body: body.text.repeat(body.repeat).into(),
+	}
+}
+
+fn with_hex_json(_: Unhex, Json(body): Json<Body>) -> Response {
+	Response {
+		body: body.text.repeat(body.repeat).into(),
+	}
+}
+
+struct Beta;
+
+impl FeatureName for Beta {
+	const NAME: &'static str = "beta";
+}
+
+#[derive(Clone, Copy)]
+struct Rollout {
+	max_count: u8,
+}
+
+impl FeatureFlags for Rollout {
+	fn is_enabled(&self, name: &str, parts: &RequestParts) -> bool {
+		name == Beta::NAME && parts.count <= self.max_count
+	}
+}
+
+fn with_feature(Feature(beta, _): Feature<Beta>) -> Response {
+	Response {
+		body: format!("beta: {beta}").into(),
+	}
+}
+
+#[derive(Clone, Copy)]
+struct Envelope;
+
+impl ResponseMapper for Envelope {
+	fn map_response(&self, parts: &RequestParts, response: Response) -> Response {
+		let ResponseBody::Full(bytes) = &response.body else {
+			return response;
+		};
+		let Ok(data) = std::str::from_utf8(bytes) else {
+			return response;
+		};
+
+		Response {
+			body: serde_json::json!({ "count": parts.count, "data": data })
+				.to_string()
+				.into(),
+		}
+	}
+}
+
+#[derive(Clone)]
+struct Accounts(Arc<MemoCache<u8, String>>);
+
+impl AsRef<MemoCache<u8, String>> for Accounts {
+	fn as_ref(&self) -> &MemoCache<u8, String> {
+		&self.0
+	}
+}
+
+fn with_account(Memo(account, _): Memo<u8, String>) -> Response {
+	Response {
+		body: account.into(),
+	}
+}
+
+fn with_sensitive_count(count: Sensitive<Count>) -> Response {
+	Response {
+		body: format!("count: {count:?}").into(),
+	}
+}
+
+fn with_sensitive_token(Sensitive(Token(token)): Sensitive<Token>) -> Response {
+	Response {
+		body: format!("token: {token}").into(),
+	}
+}
+
+fn with_state_and_sensitive_json(
+	State(state): State<u8>,
+	Sensitive(Json(body)): Sensitive<Json<Body>>,
+) -> Response {
+	Response {
+		body: format!("state: {state}, text: {}", body.text).into(),
+	}
+}
+
+fn echo(Expensive(expensive): Expensive) -> Response {
+	Response {
+		body: expensive.into(),
+	}
+}
+
+fn empty() -> Response {
+	Response { body: ().into() }
+}
+
+fn side_effect() {}
+
+fn with_side_effect(Count(_): Count) {}
+
+fn with_stream(Json(body): Json<Body>) -> Response {
+	let chunks = std::iter::repeat_n(body.text.into_bytes(), body.repeat);
+
+	Response {
+		body: ResponseBody::Stream(Box::new(chunks)),
 	}
 }
 
@@ -150,6 +635,114 @@ where
 	move |req, state| handler.call(req, state)
 }
 
+trait HandlerWithoutStateExt<T>: Handler<T, ()> + Copy {
+	fn into_route(self) -> impl Fn(Request) -> Response {
+		move |req| self.call(req, ())
+	}
+}
+
+impl<H, T> HandlerWithoutStateExt<T> for H where H: Handler<T, ()> + Copy {}
+
+fn override_state<S, T, R, F>(route: R, resolve: F) -> impl Fn(Request, S) -> Response
+where
+	R: Fn(Request, T) -> Response,
+	F: Fn(&RequestParts, S) -> T,
+{
+	move |req, state| {
+		let state = resolve(&req.parts, state);
+		route(req, state)
+	}
+}
+
+trait ResponseMapper {
+	fn map_response(&self, parts: &RequestParts, response: Response) -> Response;
+}
+
+fn map_response<S, R>(route: R) -> impl Fn(Request, S) -> Response
+where
+	S: ResponseMapper + Clone,
+	R: Fn(Request, S) -> Response,
+{
+	move |req, state| {
+		let parts = req.parts;
+		let response = route(req, state.clone());
+
+		state.map_response(&parts, response)
+	}
+}
+
+/// Shadow requests run on one worker thread behind a queue of `capacity`, and
+/// are dropped while it is full. A panicking shadow only loses its own request. Threads are unavailable on `wasm32-wasi`, so
+/// this combinator is not supported there.
+fn mirror<S, R, M>(
+	route: R,
+	shadow: M,
+	every: NonZeroUsize,
+	capacity: usize,
+) -> impl Fn(Request, S) -> Response
+where
+	S: Clone + Send + 'static,
+	R: Fn(Request, S) -> Response,
+	M: Fn(Request, S) -> Response + Send + 'static,
+{
+	let (queue, jobs) = mpsc::sync_channel::<(Request, S)>(capacity);
+	let seen = AtomicUsize::new(0);
+
+	thread::spawn(move || {
+		for (req, state) in jobs {
+			let _ = panic::catch_unwind(AssertUnwindSafe(|| shadow(req, state)));
+		}
+	});
+
+	move |req, state| {
+		if seen
+			.fetch_add(1, Ordering::Relaxed)
+			.is_multiple_of(every.get())
+		{
+			let _ = queue.try_send((req.clone(), state.clone()));
+		}
+
+		route(req, state)
+	}
+}
+
+fn record<S, R>(route: R, path: impl AsRef<Path>) -> impl Fn(Request, S) -> Response
+where
+	R: Fn(Request, S) -> Response,
+{
+	let path = path.as_ref().to_path_buf();
+
+	move |req, state| {
+		if let Ok(mut line) = serde_json::to_vec(&req) {
+			line.push(b'\n');
+
+			let _ = OpenOptions::new()
+				.create(true)
+				.append(true)
+				.open(&path)
+				.and_then(|mut file| file.write_all(&line));
+		}
+
+		route(req, state)
+	}
+}
+
+fn replay<S, R>(route: R, path: impl AsRef<Path>, state: S) -> Vec<Response>
+where
+	S: Clone,
+	R: Fn(Request, S) -> Response,
+{
+	let recording = fs::read_to_string(path).expect("expected readable recording");
+
+	recording
+		.lines()
+		.map(|line| {
+			let req = serde_json::from_str(line).expect("expected valid recording");
+			route(req, state.clone())
+		})
+		.collect()
+}
+
 fn main() {
 	let state = 42;
 	let request = Request {
@@ -164,20 +757,340 @@ fn main() {
 	let route = get(simple);
 	let response = route(request.clone(), state);
 
-	assert_eq!(response.content, "Hello, world!");
+	assert!(matches!(
+		response.body,
+		ResponseBody::Full(Cow::Borrowed(_))
+	));
+	assert_eq!(response.body.into_bytes(), b"Hello, world!");
+
+	let route = simple.into_route();
+	let response = route(request.clone());
+
+	assert_eq!(response.body.into_bytes(), b"Hello, world!");
+
+	let route = with_json.into_route();
+	let response = route(request.clone());
+
+	assert_eq!(response.body.into_bytes(), b"hihihihihihi");
+
+	let route = map_response(get(simple));
+	let response = route(request.clone(), Envelope);
+
+	assert_eq!(
+		response.body.into_bytes(),
+		br#"{"count":10,"data":"Hello, world!"}"#
+	);
+
+	let route = map_response(get(echo));
+	let response = route(
+		Request {
+			expensive: vec![0xff],
+			..request.clone()
+		},
+		Envelope,
+	);
+
+	assert_eq!(response.body.into_bytes(), [0xff]);
+
+	let route = map_response(get(with_stream));
+	let response = route(request.clone(), Envelope);
+
+	assert!(matches!(response.body, ResponseBody::Stream(_)));
 
 	let route = get(with_count_and_state);
 	let response = route(request.clone(), state);
 
-	assert_eq!(response.content, "state: 42, count: 10");
+	assert_eq!(response.body.into_bytes(), b"state: 42, count: 10");
+
+	let route = override_state(get(with_count_and_state), |parts, state: u8| {
+		state + parts.count
+	});
+	let response = route(request.clone(), state);
+
+	assert_eq!(response.body.into_bytes(), b"state: 52, count: 10");
+
+	let route = get(with_feature);
+	let response = route(request.clone(), Rollout { max_count: 10 });
+
+	assert_eq!(response.body.into_bytes(), b"beta: true");
+
+	let response = route(request.clone(), Rollout { max_count: 5 });
+
+	assert_eq!(response.body.into_bytes(), b"beta: false");
+
+	let lookups = Arc::new(AtomicUsize::new(0));
+	let accounts = Accounts(Arc::new(MemoCache::new(
+		Duration::from_secs(60),
+		|parts| parts.count,
+		{
+			let lookups = Arc::clone(&lookups);
+
+			move |count| {
+				lookups.fetch_add(1, Ordering::Relaxed);
+				format!("account-{count}")
+			}
+		},
+	)));
+
+	let route = get(with_account);
+
+	for _ in 0..2 {
+		let response = route(request.clone(), accounts.clone());
+
+		assert_eq!(response.body.into_bytes(), b"account-10");
+	}
+
+	assert_eq!(lookups.load(Ordering::Relaxed), 1);
+
+	let expiring = MemoCache::new(Duration::ZERO, |parts| parts.count, |count| *count);
+
+	for count in 0..4 {
+		assert_eq!(expiring.get(&RequestParts { count }), count);
+	}
+
+	assert_eq!(expiring.entries().values.len(), 1);
+
+	let lasting = MemoCache::new(Duration::from_secs(60), |parts| parts.count, |count| *count);
+
+	for count in 0..4 {
+		assert_eq!(lasting.get(&RequestParts { count }), count);
+	}
+
+	assert_eq!(lasting.entries().values.len(), 4);
+
+	let (mirrored, received) = mpsc::channel();
+	let shadow = move |req: Request, state| {
+		mirrored.send(req.parts.count).unwrap();
+		get(with_json)(req, state)
+	};
+
+	let every = NonZeroUsize::new(2).unwrap();
+	let route = mirror(get(with_json), shadow, every, 8);
+
+	for _ in 0..3 {
+		let response = route(request.clone(), state);
+
+		assert_eq!(response.body.into_bytes(), b"hihihihihihi");
+	}
+
+	drop(route);
+
+	assert_eq!(received.iter().collect::<Vec<_>>(), [10, 10]);
+
+	let (mirrored, received) = mpsc::channel();
+	let shadow = move |req: Request, state| {
+		let count = req.parts.count;
+		let response = get(with_json)(req, state);
+
+		mirrored.send(count).unwrap();
+		response
+	};
+
+	let route = mirror(get(echo), shadow, NonZeroUsize::MIN, 8);
+	let hook = panic::take_hook();
+	panic::set_hook(Box::new(|_| {}));
+
+	let invalid = Request {
+		expensive: b"not json".to_vec(),
+		..request.clone()
+	};
+
+	route(invalid, state);
+
+	for _ in 0..3 {
+		route(request.clone(), state);
+	}
+
+	drop(route);
+
+	assert_eq!(received.iter().collect::<Vec<_>>(), [10, 10, 10]);
+
+	panic::set_hook(hook);
+
+	let recording = std::env::temp_dir().join(format!(
+		"extract-as-argument-recording-{}.jsonl",
+		std::process::id()
+	));
+	let _ = fs::remove_file(&recording);
+
+	let route = record(get(with_json), &recording);
+	let response = route(request.clone(), state);
+
+	assert_eq!(response.body.into_bytes(), b"hihihihihihi");
+
+	let responses = replay(get(with_json), &recording, state);
+
+	assert_eq!(responses.len(), 1);
+	assert!(responses
+		.into_iter()
+		.all(|response| response.body.into_bytes() == b"hihihihihihi"));
+
+	fs::remove_file(&recording).expect("expected removable recording");
+
+	let route = record(get(with_json), std::env::temp_dir());
+	let response = route(request.clone(), state);
+
+	assert_eq!(response.body.into_bytes(), b"hihihihihihi");
+
+	let route = get(with_sensitive_count);
+	let response = route(request.clone(), state);
+
+	assert_eq!(response.body.into_bytes(), b"count: [redacted]");
+
+	let route = get(with_sensitive_token);
+	let response = route(request.clone(), state);
+
+	assert_eq!(response.body.into_bytes(), b"token: 10");
+
+	let hook = panic::take_hook();
+	panic::set_hook(Box::new(|_| {}));
+
+	let rejection = panic::catch_unwind(|| {
+		route(
+			Request {
+				parts: RequestParts { count: 11 },
+				..request.clone()
+			},
+			state,
+		)
+	})
+	.err()
+	.and_then(|payload| payload.downcast::<String>().ok())
+	.expect("expected a rejection message");
+
+	panic::set_hook(hook);
+
+	assert!(rejection.contains("Token"));
+	assert!(!rejection.contains("secret"));
+
+	let route = get(with_state_and_sensitive_json);
+	let response = route(request.clone(), state);
+
+	assert_eq!(response.body.into_bytes(), b"state: 42, text: hi");
+
+	let route = get(with_non_zero_count);
+	let response = route(request.clone(), state);
+
+	assert_eq!(response.body.into_bytes(), b"count: 10");
+
+	let hook = panic::take_hook();
+	panic::set_hook(Box::new(|_| {}));
+
+	let rejection = panic::catch_unwind(|| {
+		route(
+			Request {
+				parts: RequestParts { count: 0 },
+				..request.clone()
+			},
+			state,
+		)
+	})
+	.err()
+	.and_then(|payload| payload.downcast::<String>().ok())
+	.expect("expected a rejection message");
+
+	panic::set_hook(hook);
+
+	assert!(rejection.contains("NonZeroCount"));
+
+	let route = get(with_visit_and_count);
+	let response = route(request.clone(), state);
+
+	assert_eq!(response.body.into_bytes(), b"count: 11");
+
+	let response = route(
+		Request {
+			parts: RequestParts { count: u8::MAX },
+			..request.clone()
+		},
+		state,
+	);
+
+	assert_eq!(response.body.into_bytes(), b"count: 255");
 
 	let route = get(with_state_and_expensive);
 	let response = route(request.clone(), state);
 
-	assert_eq!(response.content, "state: 42, expensive: 37");
+	assert_eq!(response.body.into_bytes(), b"state: 42, expensive: 37");
+
+	let route = get(with_json);
+	let response = route(request.clone(), state);
+
+	assert_eq!(response.body.into_bytes(), b"hihihihihihi");
+
+	let route = get(empty);
+	let response = route(request.clone(), state);
+
+	assert!(matches!(response.body, ResponseBody::Empty));
+	assert_eq!(
+		serde_json::to_string(&route(request.clone(), state).snapshot()).unwrap(),
+		r#"{"body":"empty"}"#
+	);
 
 	let route = get(with_json);
 	let response = route(request.clone(), state);
 
-	assert_eq!(response.content, "hihihihihihi");
+	assert_eq!(
+		serde_json::to_string(&response.snapshot()).unwrap(),
+		r#"{"body":{"text":"hihihihihihi"}}"#
+	);
+
+	let route = get(echo);
+	let response = route(
+		Request {
+			expensive: vec![0xff],
+			..request.clone()
+		},
+		state,
+	);
+
+	assert_eq!(
+		serde_json::to_string(&response.snapshot()).unwrap(),
+		r#"{"body":{"binary":[255]}}"#
+	);
+
+	let route = get(side_effect);
+	let response = route(request.clone(), state);
+
+	assert!(matches!(response.body, ResponseBody::Empty));
+
+	let route = get(with_side_effect);
+	let response = route(request.clone(), state);
+
+	assert!(matches!(response.body, ResponseBody::Empty));
+
+	let route = get(with_stream);
+	let response = route(request.clone(), state);
+
+	assert!(matches!(response.body, ResponseBody::Stream(_)));
+	assert_eq!(response.body.into_bytes(), b"hihihihihihi");
+
+	let nonce = 7;
+	let encrypted = Request {
+		expensive: std::iter::once(nonce)
+			.chain(request.expensive.iter().map(|byte| byte ^ state ^ nonce))
+			.chain(std::iter::once(0xaa))
+			.collect(),
+		..request.clone()
+	};
+
+	let route = get(with_decrypted_json);
+	let response = route(encrypted, state);
+
+	assert_eq!(response.body.into_bytes(), b"hihihihihihi");
+
+	let hex = Request {
+		expensive: request
+			.expensive
+			.iter()
+			.map(|byte| format!("{byte:02x}"))
+			.collect::<String>()
+			.into_bytes(),
+		..request.clone()
+	};
+
+	let route = get(with_hex_json);
+	let response = route(hex, state);
+
+	assert_eq!(response.body.into_bytes(), b"hihihihihihi");
 }